@@ -1,6 +1,7 @@
-use burn::module::Module;
+use burn::module::{Module, Param};
 use burn::nn::{Linear, LinearConfig};
-use burn::tensor::{backend::Backend, Tensor};
+use burn::tensor::{backend::Backend, Tensor, TensorData};
+use runtime::scheduler::manager::Scheduler;
 
 #[derive(Module, Debug)]
 pub struct DeepFeedForward<B: Backend> {
@@ -64,4 +65,215 @@ impl<B: Backend> DeepFeedForward<B> {
 
         self.output_layer.forward(x)
     }
+
+    /// All linear layers in forward-pass order, so code that needs to walk
+    /// them generically doesn't have to be hard-coded to
+    /// `layer_01..output_layer`.
+    pub fn layers(&self) -> impl Iterator<Item = &Linear<B>> {
+        [
+            &self.layer_01,
+            &self.layer_02,
+            &self.layer_03,
+            &self.layer_04,
+            &self.layer_05,
+            &self.layer_06,
+            &self.layer_07,
+            &self.layer_08,
+            &self.layer_09,
+            &self.layer_10,
+            &self.output_layer,
+        ]
+        .into_iter()
+    }
+
+    /// The Streaming Forward Pass: asks the `Scheduler` to make room for
+    /// one layer's weights at a time, copies those weights through the
+    /// reserved buffer it gets back, and rebuilds a `Linear` from that
+    /// round-tripped copy before running it — so the arena reservation is
+    /// genuinely what the forward pass reads from, not a side-channel byte
+    /// count next to a computation that ignores it entirely.
+    ///
+    /// Layers are double-buffered: as soon as a layer's weights are in
+    /// hand, the next layer is handed to a background prefetch worker
+    /// before this layer's `forward()` runs, so its load overlaps with
+    /// this layer's matmul instead of happening afterward.
+    pub fn forward_streaming(
+        &self,
+        input: Tensor<B, 2>,
+        scheduler: &mut Scheduler,
+    ) -> Result<Tensor<B, 2>, StreamingForwardError> {
+        let device = input.device();
+        let mut x = input;
+        let layers: Vec<&Linear<B>> = self.layers().collect();
+        let layer_count = layers.len();
+
+        // Prime the pipeline: get the first layer's load started before we
+        // need it.
+        scheduler.prefetch(0, layer_param_bytes(layers[0]));
+
+        for (index, layer) in layers.iter().copied().enumerate() {
+            let size_bytes = layer_param_bytes(layer);
+            let ptr = scheduler
+                .await_layer(index)
+                .map_err(|_| StreamingForwardError::LayerDidNotFit { layer_index: index, size_bytes })?;
+
+            let params = extract_layer_params(layer);
+            // Safety: `ptr` points at `size_bytes` bytes the scheduler just
+            // reserved for this layer, which matches what this layer's
+            // weight plus bias `Vec`s occupy.
+            let params = unsafe { params.round_trip_through(ptr) };
+            let streamed_layer = params.into_linear(&device);
+
+            let is_output_layer = index + 1 == layer_count;
+            if !is_output_layer {
+                // Kick off the next layer's load now, so it overlaps with
+                // this layer's forward() below instead of waiting for it.
+                scheduler.prefetch(index + 1, layer_param_bytes(layers[index + 1]));
+            }
+
+            x = streamed_layer.forward(x);
+            if !is_output_layer {
+                x = burn::tensor::activation::relu(x);
+            }
+
+            // Done with this layer; free its slot so later layers can
+            // reuse it.
+            scheduler.evict(index);
+        }
+
+        Ok(x)
+    }
+}
+
+/// Why `forward_streaming` couldn't finish.
+#[derive(Debug)]
+pub enum StreamingForwardError {
+    /// Even after the backing pool's eviction/spill tricks, this layer's
+    /// weights didn't fit.
+    LayerDidNotFit { layer_index: usize, size_bytes: usize },
+}
+
+/// A layer's weight (and optional bias) pulled out of its `Linear` as flat
+/// `f32`s, so `forward_streaming` can hand real bytes to the scheduler
+/// instead of only counting them.
+struct LayerParams {
+    weight_dims: [usize; 2],
+    weight: Vec<f32>,
+    bias: Option<Vec<f32>>,
+}
+
+impl LayerParams {
+    /// Copy this layer's weights into the scheduler-reserved buffer at
+    /// `ptr`, then read them back out, so the values `into_linear` builds
+    /// from genuinely passed through the arena rather than being reused
+    /// straight from the original `Vec`s.
+    ///
+    /// # Safety
+    /// `ptr` must point at at least as many writable, properly aligned
+    /// bytes as this layer's weight plus bias `Vec`s occupy (what
+    /// `layer_param_bytes` computes for the layer this was extracted from).
+    unsafe fn round_trip_through(&self, ptr: *mut u8) -> LayerParams {
+        let weight_bytes = std::mem::size_of_val(self.weight.as_slice());
+        std::ptr::copy_nonoverlapping(self.weight.as_ptr().cast::<u8>(), ptr, weight_bytes);
+
+        if let Some(bias) = &self.bias {
+            let bias_bytes = std::mem::size_of_val(bias.as_slice());
+            std::ptr::copy_nonoverlapping(bias.as_ptr().cast::<u8>(), ptr.add(weight_bytes), bias_bytes);
+        }
+
+        let mut weight = vec![0f32; self.weight.len()];
+        std::ptr::copy_nonoverlapping(ptr.cast::<f32>(), weight.as_mut_ptr(), weight.len());
+
+        let bias = self.bias.as_ref().map(|original| {
+            let mut bias = vec![0f32; original.len()];
+            let src = ptr.add(weight_bytes).cast::<f32>();
+            std::ptr::copy_nonoverlapping(src, bias.as_mut_ptr(), bias.len());
+            bias
+        });
+
+        LayerParams { weight_dims: self.weight_dims, weight, bias }
+    }
+
+    fn into_linear<B: Backend>(self, device: &B::Device) -> Linear<B> {
+        let [in_features, out_features] = self.weight_dims;
+        let weight_data = TensorData::new(self.weight, [in_features, out_features]);
+        let weight = Param::from_tensor(Tensor::<B, 2>::from_data(weight_data, device));
+
+        let bias = self.bias.map(|bias| {
+            let bias_data = TensorData::new(bias, [out_features]);
+            Param::from_tensor(Tensor::<B, 1>::from_data(bias_data, device))
+        });
+
+        Linear { weight, bias }
+    }
+}
+
+/// Pull a layer's weight (and bias, if any) out as flat `f32`s via its
+/// `Module` record, the same mechanism burn itself uses to save/load
+/// weights, so `forward_streaming` has real bytes to push through the
+/// arena instead of just a parameter count.
+fn extract_layer_params<B: Backend>(layer: &Linear<B>) -> LayerParams {
+    let record = layer.clone().into_record();
+
+    let weight_data = record.weight.val().into_data();
+    let weight_dims = [weight_data.shape[0], weight_data.shape[1]];
+    let weight = weight_data.into_vec::<f32>().expect("Linear weight should be f32");
+
+    let bias = record
+        .bias
+        .map(|bias| bias.val().into_data().into_vec::<f32>().expect("Linear bias should be f32"));
+
+    LayerParams { weight_dims, weight, bias }
+}
+
+/// How many bytes `extract_layer_params` would pull out of this layer,
+/// without actually copying its weights — cheap enough to call a layer
+/// ahead so `forward_streaming` knows what to prefetch.
+fn layer_param_bytes<B: Backend>(layer: &Linear<B>) -> usize {
+    layer.num_params() * std::mem::size_of::<f32>()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use burn::backend::NdArray;
+
+    type TestBackend = NdArray<f32>;
+
+    fn tiny_config() -> DeepFeedForwardConfig {
+        DeepFeedForwardConfig { input_size: 4, hidden_size: 8, output_size: 2 }
+    }
+
+    #[test]
+    fn test_forward_streaming_matches_forward() {
+        let device = Default::default();
+        let model = tiny_config().init::<TestBackend>(&device);
+        let input = Tensor::<TestBackend, 2>::from_floats([[0.1, 0.2, 0.3, 0.4]], &device);
+
+        let expected = model.forward(input.clone());
+
+        let mut scheduler = Scheduler::boot(8).unwrap(); // plenty of room for this tiny model
+        let actual = model
+            .forward_streaming(input, &mut scheduler)
+            .expect("every layer should fit");
+
+        assert_eq!(
+            expected.into_data().into_vec::<f32>().unwrap(),
+            actual.into_data().into_vec::<f32>().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_forward_streaming_errors_instead_of_panicking_when_a_layer_does_not_fit() {
+        let device = Default::default();
+        let model = tiny_config().init::<TestBackend>(&device);
+        let input = Tensor::<TestBackend, 2>::from_floats([[0.1, 0.2, 0.3, 0.4]], &device);
+
+        // Too small to hold even the first layer's weights.
+        let mut scheduler = Scheduler::boot(0).unwrap();
+        match model.forward_streaming(input, &mut scheduler) {
+            Err(StreamingForwardError::LayerDidNotFit { layer_index: 0, .. }) => {}
+            other => panic!("Expected the first layer to fail to fit, got {:?}", other),
+        }
+    }
 }
\ No newline at end of file
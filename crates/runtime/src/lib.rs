@@ -4,3 +4,4 @@ pub mod monitor;
 
 // Re-export specific items to make imports cleaner
 pub use memory::arena::{ModelArena, ArenaError};
+pub use memory::pool::{ArenaPool, MemoryPool, PoolError, Reservation, SpillingPool};
@@ -1,30 +1,123 @@
 use std::alloc::{Layout, alloc, dealloc};
 use std::ptr::NonNull;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// A single sub-allocation inside the arena's backing buffer.
+/// The arena keeps these in a list sorted by `offset`, which lets
+/// `free` find the blocks immediately before/after it by index to coalesce.
+#[derive(Debug, Clone, Copy)]
+struct Block {
+    offset: usize,
+    size: usize,
+    free: bool,
+}
+
+/// Allocation bookkeeping for a `ModelArena`, safe to read concurrently
+/// from a background thread (e.g. `SystemMonitor`). The counters are only
+/// kept up to date behind the `stats` cargo feature, so a release build
+/// that doesn't care about attribution doesn't pay for the extra atomic
+/// traffic on every `alloc`/`free`.
+#[derive(Default)]
+pub struct ArenaStats {
+    bytes_in_use: AtomicUsize,
+    peak_bytes: AtomicUsize,
+    total_allocated: AtomicUsize,
+    live_allocations: AtomicUsize,
+}
+
+impl ArenaStats {
+    pub fn bytes_in_use(&self) -> usize {
+        self.bytes_in_use.load(Ordering::Relaxed)
+    }
+
+    pub fn peak_bytes(&self) -> usize {
+        self.peak_bytes.load(Ordering::Relaxed)
+    }
+
+    pub fn total_allocated(&self) -> usize {
+        self.total_allocated.load(Ordering::Relaxed)
+    }
+
+    pub fn live_allocations(&self) -> usize {
+        self.live_allocations.load(Ordering::Relaxed)
+    }
+
+    #[cfg(feature = "stats")]
+    fn record_alloc(&self, size: usize) {
+        let in_use = self.bytes_in_use.fetch_add(size, Ordering::Relaxed) + size;
+        self.total_allocated.fetch_add(size, Ordering::Relaxed);
+        self.live_allocations.fetch_add(1, Ordering::Relaxed);
+        self.peak_bytes.fetch_max(in_use, Ordering::Relaxed);
+    }
+
+    #[cfg(feature = "stats")]
+    fn record_free(&self, size: usize) {
+        self.bytes_in_use.fetch_sub(size, Ordering::Relaxed);
+        self.live_allocations.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    #[cfg(feature = "stats")]
+    fn record_reset(&self) {
+        self.bytes_in_use.store(0, Ordering::Relaxed);
+        self.live_allocations.store(0, Ordering::Relaxed);
+    }
+
+    /// A plain-data copy of the current counters. Alloc-error handlers get
+    /// this instead of the live `ArenaStats` since the atomics inside it
+    /// aren't `Clone`.
+    pub fn snapshot(&self) -> ArenaStatsSnapshot {
+        ArenaStatsSnapshot {
+            bytes_in_use: self.bytes_in_use(),
+            peak_bytes: self.peak_bytes(),
+            total_allocated: self.total_allocated(),
+            live_allocations: self.live_allocations(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ArenaStatsSnapshot {
+    pub bytes_in_use: usize,
+    pub peak_bytes: usize,
+    pub total_allocated: usize,
+    pub live_allocations: usize,
+}
 
 /// A specialized memory allocator for Model Tensors.
-/// It mimics a "Linear Allocator" - incredibly fast, but must be reset often.
+/// Started out as a pure "Linear Allocator" that could only be reclaimed
+/// via `reset()`. It's now backed by a free-list so individual layers can
+/// be released and their space reused without discarding everything else
+/// that's resident.
 pub struct ModelArena {
     memory_start: NonNull<u8>,
     capacity: usize,
-    offset: usize, // Current pointer to free memory
+    offset: usize, // Bump pointer: the high-water mark of memory ever touched
+    blocks: Vec<Block>, // Sub-allocation ledger, sorted by offset
+    stats: Arc<ArenaStats>,
+    /// Called with the failing `Layout` and current usage whenever `alloc`
+    /// can't satisfy a request, instead of the runtime panicking. Defaults
+    /// to just logging if nothing is registered.
+    alloc_error_handler: Option<Box<dyn Fn(Layout, ArenaStatsSnapshot) + Send>>,
 }
 
 #[derive(Debug)]
 pub enum ArenaError {
-    OutOfMemory { requested: usize, available: usize },
+    OutOfMemory { requested: usize, available: usize, layout: Layout },
     AllocationFailed,
+    InvalidFree { offset: usize },
 }
 
 impl ModelArena {
     /// Request a large chunk of RAM from the OS once (at startup).
     pub fn new(capacity_mb: usize) -> Result<Self, ArenaError> {
         let capacity_bytes = capacity_mb * 1024 * 1024;
-        
+
         // We use unsafe Rust to allocate raw memory with specific alignment
         // This is pure Systems Engineering.
         let layout = Layout::from_size_align(capacity_bytes, 32)
             .map_err(|_| ArenaError::AllocationFailed)?;
-            
+
         let ptr = unsafe { alloc(layout) };
         let memory_start = NonNull::new(ptr).ok_or(ArenaError::AllocationFailed)?;
 
@@ -32,21 +125,67 @@ impl ModelArena {
             memory_start,
             capacity: capacity_bytes,
             offset: 0,
+            blocks: Vec::new(),
+            stats: Arc::new(ArenaStats::default()),
+            alloc_error_handler: None,
         })
     }
 
+    /// Register a handler invoked with the failing `Layout` and current
+    /// usage whenever `alloc` can't satisfy a request. Lets an embedder
+    /// trigger a checkpoint, force an eviction, or abort cleanly instead of
+    /// the runtime panicking on its behalf.
+    pub fn set_alloc_error_handler(&mut self, handler: Box<dyn Fn(Layout, ArenaStatsSnapshot) + Send>) {
+        self.alloc_error_handler = Some(handler);
+    }
+
     /// The "malloc" replacement.
     /// Returns a pointer to the start of the valid memory block.
     pub fn alloc(&mut self, size: usize) -> Result<*mut u8, ArenaError> {
+        // 1. First-fit: look for a free block big enough to reuse before
+        // touching memory we haven't bumped into yet.
+        if let Some(index) = self.blocks.iter().position(|b| b.free && b.size >= size) {
+            let block = self.blocks[index];
+
+            if block.size > size {
+                // Split the block: shrink it to the requested size and
+                // keep the remainder around as a new free block.
+                self.blocks[index].size = size;
+                self.blocks.insert(index + 1, Block {
+                    offset: block.offset + size,
+                    size: block.size - size,
+                    free: true,
+                });
+            }
+            self.blocks[index].free = false;
+
+            let ptr = unsafe { self.memory_start.as_ptr().add(self.blocks[index].offset) };
+            unsafe {
+                std::ptr::write_bytes(ptr, 0, size);
+            }
+            #[cfg(feature = "stats")]
+            self.stats.record_alloc(size);
+            return Ok(ptr);
+        }
+
+        // 2. Nothing in the free list fits; fall back to the bump pointer.
         if self.offset + size > self.capacity {
-            return Err(ArenaError::OutOfMemory { 
-                requested: size, 
-                available: self.capacity - self.offset 
-            });
+            let available = self.capacity - self.offset;
+            let layout = Layout::from_size_align(size, 1).unwrap_or_else(|_| Layout::new::<u8>());
+
+            match &self.alloc_error_handler {
+                Some(handler) => handler(layout, self.stats.snapshot()),
+                None => println!(
+                    "[ModelArena] OOM: failed to allocate {} bytes ({} available)",
+                    size, available
+                ),
+            }
+
+            return Err(ArenaError::OutOfMemory { requested: size, available, layout });
         }
 
-        let ptr = unsafe { 
-            self.memory_start.as_ptr().add(self.offset) 
+        let ptr = unsafe {
+            self.memory_start.as_ptr().add(self.offset)
         };
 
         // --- ADD THIS BLOCK ---
@@ -57,19 +196,73 @@ impl ModelArena {
         }
         // ----------------------
 
+        self.blocks.push(Block { offset: self.offset, size, free: false });
         self.offset += size;
+        #[cfg(feature = "stats")]
+        self.stats.record_alloc(size);
         Ok(ptr)
     }
 
+    /// Release a single allocation so its space can be reused by a later
+    /// `alloc`, coalescing with neighboring free blocks to fight
+    /// fragmentation. This is what lets the scheduler evict exactly one
+    /// layer instead of resetting the whole arena.
+    ///
+    /// # Safety
+    /// `ptr` must be a pointer this arena previously handed back from
+    /// `alloc` and not already freed; it's passed to `offset_from`, which
+    /// requires it to lie inside this arena's buffer, to recover which
+    /// block it belongs to.
+    pub unsafe fn free(&mut self, ptr: *mut u8) -> Result<(), ArenaError> {
+        let offset = unsafe { ptr.offset_from(self.memory_start.as_ptr()) } as usize;
+        let index = self.blocks.iter()
+            .position(|b| b.offset == offset)
+            .ok_or(ArenaError::InvalidFree { offset })?;
+
+        #[cfg(feature = "stats")]
+        let freed_size = self.blocks[index].size;
+
+        self.blocks[index].free = true;
+
+        // Coalesce with the following block first so `index` stays valid.
+        if index + 1 < self.blocks.len() && self.blocks[index + 1].free {
+            let next = self.blocks.remove(index + 1);
+            self.blocks[index].size += next.size;
+        }
+        if index > 0 && self.blocks[index - 1].free {
+            let merged = self.blocks.remove(index);
+            self.blocks[index - 1].size += merged.size;
+        }
+
+        #[cfg(feature = "stats")]
+        self.stats.record_free(freed_size);
+
+        Ok(())
+    }
+
     /// Reset the arena. We don't "free" individual objects.
-    /// We just move the pointer back to 0. (Extremely fast).
+    /// We just move the pointer back to 0 and drop the free list. (Extremely fast).
     pub fn reset(&mut self) {
         self.offset = 0;
+        self.blocks.clear();
+        #[cfg(feature = "stats")]
+        self.stats.record_reset();
     }
-    
-    /// Metrics: How much memory is currently used?
+
+    /// Metrics: How much memory is currently in use (excludes free blocks)?
     pub fn used_bytes(&self) -> usize {
-        self.offset
+        self.blocks.iter().filter(|b| !b.free).map(|b| b.size).sum()
+    }
+
+    /// Total bytes this arena was created with.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// A handle to this arena's live allocation statistics, suitable for
+    /// handing to a background reporter like `SystemMonitor`.
+    pub fn stats(&self) -> Arc<ArenaStats> {
+        self.stats.clone()
     }
 }
 
@@ -101,15 +294,15 @@ mod tests {
     #[test]
     fn test_allocation_success() {
         let mut arena = ModelArena::new(10).unwrap(); // 10MB
-        
+
         // Allocate 1KB
         let ptr = arena.alloc(1024).expect("Should fit");
         assert!(!ptr.is_null());
         assert_eq!(arena.used_bytes(), 1024);
-        
+
         // Allocate another 1KB
         let ptr2 = arena.alloc(1024).expect("Should fit");
-        
+
         // Guard Rail: Pointers must not overlap
         // The distance between pointers should be exactly 1024 bytes
         unsafe {
@@ -120,18 +313,19 @@ mod tests {
     #[test]
     fn test_out_of_memory() {
         let mut arena = ModelArena::new(1).unwrap(); // 1MB Total
-        
+
         // Allocate 0.6MB (Success)
         let _ = arena.alloc(600 * 1024).unwrap();
-        
+
         // Allocate 0.5MB (Fail: 0.6 + 0.5 > 1.0)
         let result = arena.alloc(500 * 1024);
-        
+
         match result {
-            Err(ArenaError::OutOfMemory { requested, available }) => {
+            Err(ArenaError::OutOfMemory { requested, available, layout }) => {
                 assert_eq!(requested, 500 * 1024);
                 // Available should be 1MB - 600KB = 424KB (roughly)
                 assert!(available < 500 * 1024);
+                assert_eq!(layout.size(), 500 * 1024);
             }
             _ => panic!("Should have failed with OOM"),
         }
@@ -141,12 +335,113 @@ mod tests {
     fn test_reset_behavior() {
         let mut arena = ModelArena::new(1).unwrap();
         let _ = arena.alloc(1024).unwrap();
-        
+
         // Reset
         arena.reset();
         assert_eq!(arena.used_bytes(), 0);
-        
+
         // Should be able to allocate again at the start
         let _ = arena.alloc(1024 * 1024).unwrap(); // Fill entire arena
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_free_and_reuse_exact_fit() {
+        let mut arena = ModelArena::new(1).unwrap();
+
+        let first = arena.alloc(1024).unwrap();
+        let _second = arena.alloc(1024).unwrap();
+        assert_eq!(arena.used_bytes(), 2048);
+
+        unsafe { arena.free(first) }.unwrap();
+        assert_eq!(arena.used_bytes(), 1024);
+
+        // A same-size allocation should land in the freed slot instead of
+        // bumping the offset forward.
+        let reused = arena.alloc(1024).unwrap();
+        assert_eq!(reused, first);
+        assert_eq!(arena.used_bytes(), 2048);
+    }
+
+    #[test]
+    fn test_free_split_keeps_remainder_available() {
+        let mut arena = ModelArena::new(1).unwrap();
+
+        let big = arena.alloc(4096).unwrap();
+        unsafe { arena.free(big) }.unwrap();
+
+        // A smaller allocation should split the freed block, reusing its
+        // start and leaving the remainder free for a later caller.
+        let small = arena.alloc(1024).unwrap();
+        assert_eq!(small, big);
+        assert_eq!(arena.used_bytes(), 1024);
+
+        let remainder = arena.alloc(1024).unwrap();
+        unsafe {
+            assert_eq!(small.add(1024), remainder);
+        }
+    }
+
+    #[test]
+    fn test_free_coalesces_adjacent_blocks() {
+        let mut arena = ModelArena::new(1).unwrap();
+
+        let first = arena.alloc(1024).unwrap();
+        let _second = arena.alloc(1024).unwrap();
+        let _third = arena.alloc(1024).unwrap();
+
+        unsafe { arena.free(first) }.unwrap();
+        unsafe { arena.free(_second) }.unwrap();
+
+        // The two freed neighbors should have merged into one 2048-byte
+        // block, so a 2048-byte request fits without touching new memory.
+        let merged = arena.alloc(2048).unwrap();
+        assert_eq!(merged, first);
+    }
+
+    #[cfg(feature = "stats")]
+    #[test]
+    fn test_stats_track_peak_and_live_allocations() {
+        let mut arena = ModelArena::new(1).unwrap();
+        let stats = arena.stats();
+
+        let first = arena.alloc(1024).unwrap();
+        let _second = arena.alloc(2048).unwrap();
+        assert_eq!(stats.bytes_in_use(), 3072);
+        assert_eq!(stats.peak_bytes(), 3072);
+        assert_eq!(stats.live_allocations(), 2);
+
+        unsafe { arena.free(first) }.unwrap();
+        assert_eq!(stats.bytes_in_use(), 2048);
+        assert_eq!(stats.live_allocations(), 1);
+        // Peak stays at the high-water mark even after freeing.
+        assert_eq!(stats.peak_bytes(), 3072);
+        assert_eq!(stats.total_allocated(), 3072);
+    }
+
+    #[test]
+    fn test_alloc_error_handler_receives_failing_layout() {
+        let mut arena = ModelArena::new(1).unwrap();
+        let _ = arena.alloc(600 * 1024).unwrap();
+
+        let seen = Arc::new(std::sync::Mutex::new(None));
+        let seen_clone = seen.clone();
+        arena.set_alloc_error_handler(Box::new(move |layout, _stats| {
+            *seen_clone.lock().unwrap() = Some(layout.size());
+        }));
+
+        let _ = arena.alloc(500 * 1024);
+        assert_eq!(*seen.lock().unwrap(), Some(500 * 1024));
+    }
+
+    #[test]
+    fn test_free_unknown_pointer_is_an_error() {
+        let mut arena = ModelArena::new(1).unwrap();
+        let ptr = arena.alloc(1024).unwrap();
+
+        let bogus = unsafe { ptr.add(4096) };
+        match unsafe { arena.free(bogus) } {
+            Err(ArenaError::InvalidFree { .. }) => {}
+            _ => panic!("Freeing a pointer with no matching block should fail"),
+        }
+    }
+}
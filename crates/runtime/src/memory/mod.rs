@@ -0,0 +1,2 @@
+pub mod arena;
+pub mod pool;
@@ -1,3 +1,4 @@
+use crate::memory::arena::ArenaStats;
 use sysinfo::{Pid, System}; // removed ProcessExt, SystemExt
 use std::sync::{Arc, atomic::{AtomicBool, Ordering}};
 use std::thread;
@@ -8,7 +9,7 @@ pub struct SystemMonitor {
 }
 
 impl SystemMonitor {
-    pub fn start(interval_ms: u64) -> Self {
+    pub fn start(interval_ms: u64, arena_stats: Arc<ArenaStats>) -> Self {
         let stop_signal = Arc::new(AtomicBool::new(false));
         let signal_clone = stop_signal.clone();
 
@@ -32,10 +33,16 @@ impl SystemMonitor {
                     let memory_bytes = process.memory(); 
                     let memory_mb = memory_bytes as f64 / 1024.0 / 1024.0;
                     let cpu_usage = process.cpu_usage();
-                    
-                    print!("\r[Monitor] OS RAM: {:.2} MB | CPU: {:.1}% ", 
-                        memory_mb, 
-                        cpu_usage
+
+                    let arena_mb = arena_stats.bytes_in_use() as f64 / 1024.0 / 1024.0;
+                    let arena_peak_mb = arena_stats.peak_bytes() as f64 / 1024.0 / 1024.0;
+
+                    print!("\r[Monitor] OS RAM: {:.2} MB | CPU: {:.1}% | Arena: {:.2} MB (peak {:.2} MB, {} live) ",
+                        memory_mb,
+                        cpu_usage,
+                        arena_mb,
+                        arena_peak_mb,
+                        arena_stats.live_allocations()
                     );
                 }
                 
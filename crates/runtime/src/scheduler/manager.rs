@@ -1,62 +1,265 @@
-use crate::memory::arena::{ModelArena, ArenaError};
+use crate::memory::arena::{ArenaError, ArenaStats, ArenaStatsSnapshot};
+use crate::memory::pool::{ArenaPool, MemoryPool, PoolError, Reservation};
+use crossbeam_deque::{Injector, Steal};
+use std::alloc::Layout;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
 
-/// The "Kernel" that manages neural network execution.
-pub struct Scheduler {
-    arena: ModelArena,
-    // We track "virtual" layers to know what is currently loaded
-    loaded_layers: Vec<usize>, 
+/// How many background worker threads drain the prefetch queue. This does
+/// not bound how many layers are resident at once: every queued prefetch
+/// that fits stays loaded until the caller `evict`s it.
+const PREFETCH_WORKERS: usize = 2;
+
+/// Where a prefetched layer's bytes should be loaded from. The workers do
+/// not read this: both variants load through the same `request_load`,
+/// which hands back whatever the backing pool puts in the slot. `Memory`
+/// covers layers a caller has already staged somewhere the scheduler
+/// doesn't need to know about.
+#[derive(Debug, Clone)]
+pub enum LoadSource {
+    Memory,
+    Disk(PathBuf),
+}
+
+struct PrefetchTask {
+    layer_id: usize,
+    size_bytes: usize,
+    #[allow(dead_code)] // not consulted yet; carried for when Disk loads stream real bytes
+    source: LoadSource,
+}
+
+/// A pointer handed from a worker thread to whoever calls `await_layer`.
+/// Safety: it only ever points at bytes backed by a `Reservation` held in
+/// `Inner::loaded_layers`, which outlives every `ReadyPtr` built from it.
+struct ReadyPtr(*mut u8);
+unsafe impl Send for ReadyPtr {}
+
+/// What a background prefetch resolved to, so `await_layer` can tell a
+/// genuine failure apart from "still in flight" instead of waiting on a
+/// condvar that will never be notified again.
+enum PrefetchOutcome {
+    Ready(ReadyPtr),
+    Failed,
+}
+
+/// A queued/in-flight prefetch never got its layer resident — the pool was
+/// out of room (and out of eviction/spill tricks) when the worker got to it.
+#[derive(Debug)]
+pub struct PrefetchFailed;
+
+/// A layer currently resident in memory. Holding the `Reservation` keeps
+/// its bytes alive; dropping it (via `evict` or `unload_all`) returns them
+/// to whichever `MemoryPool` is backing the scheduler.
+struct LoadedLayer {
+    id: usize,
+    #[allow(dead_code)] // never read directly; held only so Drop releases it
+    reservation: Reservation,
+}
+
+/// Everything about scheduler state that needs a lock, since it's now
+/// touched both by the execution thread and by background prefetch workers.
+struct Inner {
+    pool: Box<dyn MemoryPool>,
+    loaded_layers: Vec<LoadedLayer>,
+}
+
+impl Inner {
+    fn request_load(&mut self, layer_id: usize, size_bytes: usize) -> SchedulerDecision {
+        match self.pool.try_reserve(layer_id, size_bytes) {
+            Ok(reservation) => {
+                let ptr = reservation.ptr();
+                // Pin it: as far as this Scheduler is concerned the layer
+                // is now loaded, so a spilling pool must not pick it as an
+                // eviction victim behind our back — that was the original
+                // stale-pointer hazard. `evict`/`unload_all` drop the
+                // Reservation outright instead of unpinning it first.
+                reservation.pin();
+                self.loaded_layers.push(LoadedLayer { id: layer_id, reservation });
+                SchedulerDecision::LoadSuccess { ptr }
+            }
+            Err(PoolError::OutOfMemory { .. }) => SchedulerDecision::OOM,
+        }
+    }
+
+    fn evict(&mut self, layer_id: usize) {
+        if let Some(pos) = self.loaded_layers.iter().position(|l| l.id == layer_id) {
+            self.loaded_layers.remove(pos); // Reservation::drop releases the bytes.
+        }
+    }
+
+    fn unload_all(&mut self) {
+        self.loaded_layers.clear();
+    }
 }
 
 #[derive(Debug)]
 pub enum SchedulerDecision {
     LoadSuccess { ptr: *mut u8 },
-    /// The system is full, but we can make space by unloading old layers.
-    MustUnload { layer_id: usize },
-    /// Critical failure: Even if we unload everything, this layer is too big.
+    /// Critical failure: even the backing pool's eviction/spill tricks
+    /// couldn't make room for this layer.
     OOM,
 }
 
+/// The "Kernel" that manages neural network execution.
+pub struct Scheduler {
+    inner: Arc<Mutex<Inner>>,
+    injector: Arc<Injector<PrefetchTask>>,
+    ready: Arc<(Mutex<HashMap<usize, PrefetchOutcome>>, Condvar)>,
+    shutdown: Arc<AtomicBool>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+fn run_prefetch_worker(
+    injector: Arc<Injector<PrefetchTask>>,
+    inner: Arc<Mutex<Inner>>,
+    ready: Arc<(Mutex<HashMap<usize, PrefetchOutcome>>, Condvar)>,
+    shutdown: Arc<AtomicBool>,
+) {
+    while !shutdown.load(Ordering::Relaxed) {
+        match injector.steal() {
+            Steal::Success(task) => {
+                println!(
+                    "[Scheduler] Prefetching Layer {} ({} MB)",
+                    task.layer_id,
+                    task.size_bytes / 1024 / 1024
+                );
+                let decision = inner.lock().unwrap().request_load(task.layer_id, task.size_bytes);
+                let (lock, cvar) = &*ready;
+                let outcome = match decision {
+                    SchedulerDecision::LoadSuccess { ptr } => PrefetchOutcome::Ready(ReadyPtr(ptr)),
+                    SchedulerDecision::OOM => {
+                        println!("[Scheduler] Prefetch of Layer {} failed: OOM", task.layer_id);
+                        PrefetchOutcome::Failed
+                    }
+                };
+                // Always insert an outcome, even on failure: otherwise
+                // await_layer has nothing to wake up for and waits on the
+                // condvar forever.
+                lock.lock().unwrap().insert(task.layer_id, outcome);
+                cvar.notify_all();
+            }
+            Steal::Empty => thread::sleep(Duration::from_millis(1)),
+            Steal::Retry => continue,
+        }
+    }
+}
+
 impl Scheduler {
-    /// Boot the scheduler with a hard RAM limit (e.g., 128MB)
+    /// Boot the scheduler with a hard RAM limit (e.g., 128MB), backed by
+    /// the default in-RAM pool.
     pub fn boot(memory_limit_mb: usize) -> Result<Self, ArenaError> {
-        Ok(Self {
-            arena: ModelArena::new(memory_limit_mb)?,
-            loaded_layers: Vec::new(),
-        })
+        Ok(Self::boot_with_pool(Box::new(ArenaPool::new(memory_limit_mb)?)))
+    }
+
+    /// Boot with a custom backing pool, e.g. a `SpillingPool` that streams
+    /// evicted layers to disk instead of hard-failing once it's full.
+    pub fn boot_with_pool(pool: Box<dyn MemoryPool>) -> Self {
+        let inner = Arc::new(Mutex::new(Inner { pool, loaded_layers: Vec::new() }));
+        let injector = Arc::new(Injector::new());
+        let ready = Arc::new((Mutex::new(HashMap::new()), Condvar::new()));
+        let shutdown = Arc::new(AtomicBool::new(false));
+
+        let workers = (0..PREFETCH_WORKERS)
+            .map(|_| {
+                let injector = injector.clone();
+                let inner = inner.clone();
+                let ready = ready.clone();
+                let shutdown = shutdown.clone();
+                thread::spawn(move || run_prefetch_worker(injector, inner, ready, shutdown))
+            })
+            .collect();
+
+        Self { inner, injector, ready, shutdown, workers }
     }
 
     /// The core logic: "I want to load Layer X. Can I?"
+    /// The backing pool decides internally whether that means reusing a
+    /// free slot, spilling something else to disk, or giving up.
     pub fn request_load(&mut self, layer_id: usize, size_bytes: usize) -> SchedulerDecision {
         println!("[Scheduler] Request: Load Layer {} ({} MB)", layer_id, size_bytes / 1024 / 1024);
+        self.inner.lock().unwrap().request_load(layer_id, size_bytes)
+    }
 
-        // 1. Try to allocate directly
-        match self.arena.alloc(size_bytes) {
-            Ok(ptr) => {
-                self.loaded_layers.push(layer_id);
-                SchedulerDecision::LoadSuccess { ptr }
-            }
-            Err(_) => {
-                // 2. If full, check if we can unload something
-                if let Some(&old_layer) = self.loaded_layers.first() {
-                    println!("[Scheduler] Memory Full. Suggesting eviction of Layer {}", old_layer);
-                    SchedulerDecision::MustUnload { layer_id: old_layer }
-                } else {
-                    // 3. If nothing to unload, we are truly OOM
-                    SchedulerDecision::OOM
-                }
+    /// Enqueue a background load of `layer_id`, so it's already resident
+    /// by the time the execution thread is done computing on the current
+    /// layer and calls `await_layer` for it. Hand this to a worker rather
+    /// than waiting on it: that's what hides load latency behind compute.
+    pub fn prefetch(&self, layer_id: usize, size_bytes: usize) {
+        self.injector.push(PrefetchTask { layer_id, size_bytes, source: LoadSource::Memory });
+    }
+
+    /// Same as `prefetch`, but tags the task with a `LoadSource`. The
+    /// source is carried with the task and not read by the workers, so the
+    /// layer is loaded exactly as `prefetch` would load it.
+    pub fn prefetch_from(&self, layer_id: usize, size_bytes: usize, source: LoadSource) {
+        self.injector.push(PrefetchTask { layer_id, size_bytes, source });
+    }
+
+    /// Block until `layer_id`'s prefetch has completed, then hand back its
+    /// pointer. Callers must have already queued a `prefetch` for this
+    /// layer (directly, or because a worker is already draining it) —
+    /// this does not fall back to a synchronous load. Returns
+    /// `Err(PrefetchFailed)` rather than blocking forever if the worker
+    /// that picked up this layer couldn't make room for it.
+    pub fn await_layer(&mut self, layer_id: usize) -> Result<*mut u8, PrefetchFailed> {
+        let (lock, cvar) = &*self.ready;
+        let mut ready = lock.lock().unwrap();
+        loop {
+            match ready.remove(&layer_id) {
+                Some(PrefetchOutcome::Ready(ptr)) => return Ok(ptr.0),
+                Some(PrefetchOutcome::Failed) => return Err(PrefetchFailed),
+                None => ready = cvar.wait(ready).unwrap(),
             }
         }
     }
 
-    /// Free up memory (conceptually unloads a layer)
+    /// Evict exactly one resident layer, returning its bytes to the pool
+    /// for reuse.
+    pub fn evict(&mut self, layer_id: usize) {
+        println!("[Scheduler] Evicting Layer {}", layer_id);
+        self.inner.lock().unwrap().evict(layer_id);
+        self.ready.0.lock().unwrap().remove(&layer_id);
+    }
+
+    /// Free up memory (conceptually unloads every layer)
     pub fn unload_all(&mut self) {
-        println!("[Scheduler] Resetting Arena (Unloading all layers)...");
-        self.arena.reset();
-        self.loaded_layers.clear();
+        let mut inner = self.inner.lock().unwrap();
+        println!("[Scheduler] Unloading all {} resident layers...", inner.loaded_layers.len());
+        inner.unload_all();
+        self.ready.0.lock().unwrap().clear();
     }
 
     pub fn memory_usage(&self) -> usize {
-        self.arena.used_bytes()
+        self.inner.lock().unwrap().pool.used()
+    }
+
+    /// A handle to the backing pool's allocation statistics, for wiring
+    /// into a reporter like `SystemMonitor`.
+    pub fn stats(&self) -> Arc<ArenaStats> {
+        self.inner.lock().unwrap().pool.stats()
+    }
+
+    /// Register a handler invoked with the failing `Layout` and current
+    /// usage whenever the backing pool's arena fails an allocation. With a
+    /// `SpillingPool` that includes failed attempts that a spill to disk
+    /// then resolves, not only a `request_load` that ends in `OOM`.
+    /// Gives embedders a single cross-cutting hook for OOM policy instead
+    /// of each caller having to special-case `SchedulerDecision::OOM`.
+    pub fn set_alloc_error_handler(&mut self, handler: Box<dyn Fn(Layout, ArenaStatsSnapshot) + Send>) {
+        self.inner.lock().unwrap().pool.set_alloc_error_handler(handler);
+    }
+}
+
+impl Drop for Scheduler {
+    fn drop(&mut self) {
+        self.shutdown.store(true, Ordering::Relaxed);
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
     }
 }
 
@@ -66,25 +269,27 @@ mod tests {
     use super::*;
 
     #[test]
-    fn test_scheduler_eviction_policy() {
-        // Boot system with 100 bytes (Tiny!)
-        // Note: ModelArena usually aligns to 32 bytes, so we use small valid numbers
+    fn test_scheduler_reuses_evicted_slot() {
         let mut scheduler = Scheduler::boot(1).unwrap(); // 1MB
         let mb = 1024 * 1024;
 
-        // 1. Load 0.6MB (Fits)
-        match scheduler.request_load(1, (0.6 * mb as f64) as usize) {
-            SchedulerDecision::LoadSuccess { .. } => assert!(true),
+        let first_ptr = match scheduler.request_load(1, (0.6 * mb as f64) as usize) {
+            SchedulerDecision::LoadSuccess { ptr } => ptr,
             _ => panic!("First load should succeed"),
+        };
+
+        // Doesn't fit yet, since Layer 1 is still resident and this is a
+        // pure in-RAM pool with nothing to spill to.
+        match scheduler.request_load(2, (0.5 * mb as f64) as usize) {
+            SchedulerDecision::OOM => {}
+            other => panic!("Expected OOM while Layer 1 is still resident, got {:?}", other),
         }
 
-        // 2. Load 0.5MB (Should trigger Eviction, NOT OOM)
-        // Because 0.5MB fits in 1MB *if* we empty it.
+        // Evicting Layer 1 frees its slot for Layer 2 to reuse.
+        scheduler.evict(1);
         match scheduler.request_load(2, (0.5 * mb as f64) as usize) {
-            SchedulerDecision::MustUnload { layer_id } => {
-                assert_eq!(layer_id, 1); // Should suggest evicting Layer 1
-            },
-            _ => panic!("Should ask to unload"),
+            SchedulerDecision::LoadSuccess { ptr } => assert_eq!(ptr, first_ptr),
+            other => panic!("Expected reuse of the freed slot, got {:?}", other),
         }
     }
 
@@ -95,8 +300,50 @@ mod tests {
 
         // Try to load 2MB into a 1MB container
         match scheduler.request_load(1, 2 * mb) {
-            SchedulerDecision::OOM => assert!(true),
+            SchedulerDecision::OOM => {}
             _ => panic!("Should be impossible to load"),
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_prefetch_then_await_layer_returns_loaded_pointer() {
+        let mut scheduler = Scheduler::boot(1).unwrap(); // 1MB
+
+        scheduler.prefetch(1, 1024);
+        let ptr = scheduler.await_layer(1).expect("prefetch should succeed");
+        assert!(!ptr.is_null());
+        assert_eq!(scheduler.memory_usage(), 1024);
+    }
+
+    #[test]
+    fn test_double_buffering_evict_active_before_prefetching_next() {
+        let mut scheduler = Scheduler::boot(1).unwrap(); // 1MB
+        let mb = 1024 * 1024;
+
+        scheduler.prefetch(1, (0.6 * mb as f64) as usize);
+        let _active = scheduler.await_layer(1).expect("prefetch should succeed");
+
+        // Not enough headroom left for a second 0.6MB slot; evict the
+        // active layer before prefetching the next one so it can reuse the
+        // freed slot.
+        scheduler.evict(1);
+        scheduler.prefetch(2, (0.6 * mb as f64) as usize);
+        let next = scheduler.await_layer(2).expect("prefetch should succeed");
+        assert!(!next.is_null());
+    }
+
+    #[test]
+    fn test_await_layer_returns_err_instead_of_hanging_when_prefetch_oom() {
+        let mut scheduler = Scheduler::boot(1).unwrap(); // 1MB
+        let mb = 1024 * 1024;
+
+        // Nothing resident yet, so this can't be satisfied by any
+        // eviction/spill trick the default ArenaPool doesn't have.
+        scheduler.prefetch(1, 2 * mb);
+
+        match scheduler.await_layer(1) {
+            Err(PrefetchFailed) => {}
+            Ok(_) => panic!("A 2MB layer can't fit in a 1MB pool"),
+        }
+    }
+}
@@ -1,4 +1,5 @@
 use runtime::scheduler::manager::{Scheduler, SchedulerDecision};
+use runtime::memory::pool::SpillingPool;
 use runtime::monitor::SystemMonitor;
 use std::thread;
 use std::time::Duration;
@@ -6,11 +7,24 @@ use std::time::Duration;
 fn main() {
     println!("--- Booting EdgeMind Runtime ---");
 
-    // 1. Start the Background Monitor (Polls every 500ms)
-    let monitor = SystemMonitor::start(100); 
+    // 1. Initialize System (50MB Limit, spilling evicted layers to disk
+    // instead of hard-failing once the budget is spent)
+    let pool = SpillingPool::new(50, "./target/spill").expect("Failed to create spilling pool");
+    let mut system = Scheduler::boot_with_pool(Box::new(pool));
 
-    // 2. Initialize System (50MB Limit)
-    let mut system = Scheduler::boot(50).expect("Failed to boot system");
+    // Instead of panicking, log every failed arena allocation (including
+    // ones a spill then resolves) with enough detail (the failing layout +
+    // current usage) to diagnose.
+    system.set_alloc_error_handler(Box::new(|layout, stats| {
+        eprintln!(
+            "[AllocErrorHandler] Failed to allocate {} bytes (align {}) — {} bytes in use, peak {} bytes",
+            layout.size(), layout.align(), stats.bytes_in_use, stats.peak_bytes
+        );
+    }));
+
+    // 2. Start the Background Monitor (Polls every 100ms), watching the
+    // scheduler's own arena stats alongside the OS-level figures
+    let monitor = SystemMonitor::start(100, system.stats());
 
     // 3. Define the workload (Same as before)
     // We add a `sleep` here so you have time to see the monitor update!
@@ -18,29 +32,32 @@ fn main() {
         ("Layer_01", 15 * 1024 * 1024),
         ("Layer_02", 15 * 1024 * 1024),
         ("Layer_03", 15 * 1024 * 1024),
-        ("Layer_04", 15 * 1024 * 1024), // This one triggers eviction
+        ("Layer_04", 15 * 1024 * 1024), // Reuses the slot Layer_03 was evicted from
         ("Layer_05", 15 * 1024 * 1024),
     ];
 
     println!("\nStarting Inference Sequence...");
-    
+
     for (_name, size) in layers {
         // Simulate "Processing Time" so the monitor can capture the spike
-        thread::sleep(Duration::from_millis(600)); 
-
-        loop {
-            match system.request_load(0, size) {
-                SchedulerDecision::LoadSuccess { ptr: _ } => {
-                    // Note: We don't print here to avoid messing up the Monitor's \r output
-                    // Just let the Monitor show the RAM going up!
-                    break;
-                }
-                SchedulerDecision::MustUnload { layer_id: _ } => {
-                    system.unload_all(); 
-                    // Give the OS time to reclaim memory (if we were actually freeing)
-                    thread::sleep(Duration::from_millis(200));
-                }
-                SchedulerDecision::OOM => panic!("System Crash!"),
+        thread::sleep(Duration::from_millis(600));
+
+        // Evict the previous layer before loading the next one. A loaded
+        // layer is pinned for as long as the Scheduler considers it
+        // resident, so the pool will never silently spill it out from
+        // under us — we have to say explicitly when we're done with it.
+        system.evict(0);
+
+        match system.request_load(0, size) {
+            SchedulerDecision::LoadSuccess { ptr: _ } => {
+                // Note: We don't print here to avoid messing up the Monitor's \r output
+                // Just let the Monitor show the RAM going up! The previous
+                // layer was already evicted above, so its slot is reused.
+            }
+            SchedulerDecision::OOM => {
+                // The alloc-error handler above already logged the details;
+                // there's nothing more for this layer to do but skip it.
+                eprintln!("[main] Skipping layer: no room even after spilling to disk");
             }
         }
     }
@@ -48,4 +65,4 @@ fn main() {
     // Stop monitor and exit
     monitor.stop();
     println!("\n--- Inference Complete ---");
-}
\ No newline at end of file
+}